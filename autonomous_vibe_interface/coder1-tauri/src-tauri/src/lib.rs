@@ -1,14 +1,31 @@
 mod pty;
+mod pty_backend;
 
-use std::process::Command;
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
 use std::sync::{Arc, Mutex};
-use tauri::State;
-use pty::PtyManager;
+use tauri::{Emitter, Manager, State};
+use pty::{start_cleanup_task, PtyManager, PtyOptions};
 
-// Store for shell command output
+// Shared state: the PTY manager plus any streaming `execute_command`
+// children that are still running.
 struct ShellState {
-    output: Mutex<String>,
     pty_manager: Arc<PtyManager>,
+    commands: Mutex<HashMap<String, Child>>,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct CommandOutput {
+    id: String,
+    stream: &'static str,
+    data: String,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct CommandExit {
+    id: String,
+    code: Option<i32>,
 }
 
 // Simple test command
@@ -17,47 +34,118 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! Terminal is ready.", name)
 }
 
-// Execute shell command (simple version)
+fn spawn_stream_reader(app: tauri::AppHandle, command_id: String, stream: &'static str, mut pipe: impl Read + Send + 'static) {
+    std::thread::spawn(move || {
+        // Raw chunks decoded lossily, like the PTY reader in pty.rs — a
+        // `BufRead::lines()` split would turn the first invalid-UTF-8 byte
+        // anywhere in the output into an `Err` that silently ends the
+        // iterator, stopping the stream for the rest of a still-running command.
+        let mut buffer = [0u8; 4096];
+        loop {
+            match pipe.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    app.emit("command-output", CommandOutput {
+                        id: command_id.clone(),
+                        stream,
+                        data: String::from_utf8_lossy(&buffer[..n]).to_string(),
+                    }).ok();
+                }
+                Err(_) => break,
+            }
+        }
+    });
+}
+
+// Execute a shell command, streaming its output instead of blocking until
+// it finishes. Returns a `command_id` immediately; `command-output` events
+// carry stdout/stderr as they arrive and `command-exit` fires on completion.
 #[tauri::command]
-fn execute_command(command: String, state: State<ShellState>) -> Result<String, String> {
-    println!("Executing command: {}", command);
-    
-    // For now, just execute simple commands
-    let output = if cfg!(target_os = "windows") {
-        Command::new("cmd")
-            .args(["/C", &command])
-            .output()
+fn execute_command(command: String, app: tauri::AppHandle) -> Result<String, String> {
+    let command_id = uuid::Uuid::new_v4().to_string();
+    println!("Executing command: {} ({})", command, command_id);
+
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut c = Command::new("cmd");
+        c.args(["/C", &command]);
+        c
     } else {
-        Command::new("sh")
-            .args(["-c", &command])
-            .output()
+        let mut c = Command::new("sh");
+        c.args(["-c", &command]);
+        c
     };
 
-    match output {
-        Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            
-            let result = if output.status.success() {
-                stdout.to_string()
-            } else {
-                format!("{}{}", stdout, stderr)
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute command: {}", e))?;
+
+    let stdout = child.stdout.take().ok_or_else(|| "Failed to capture stdout".to_string())?;
+    let stderr = child.stderr.take().ok_or_else(|| "Failed to capture stderr".to_string())?;
+
+    {
+        let state = app.state::<ShellState>();
+        state.commands.lock().unwrap().insert(command_id.clone(), child);
+    }
+
+    spawn_stream_reader(app.clone(), command_id.clone(), "stdout", stdout);
+    spawn_stream_reader(app.clone(), command_id.clone(), "stderr", stderr);
+
+    // Poll rather than block on `wait()` so `cancel_command` can still
+    // reach the child through the same `ShellState::commands` map.
+    {
+        let app = app.clone();
+        let command_id = command_id.clone();
+        std::thread::spawn(move || {
+            let code = loop {
+                let status = {
+                    let state = app.state::<ShellState>();
+                    let mut commands = state.commands.lock().unwrap();
+                    match commands.get_mut(&command_id) {
+                        Some(child) => child.try_wait().ok().flatten(),
+                        None => break None,
+                    }
+                };
+                match status {
+                    Some(status) => break status.code(),
+                    None => std::thread::sleep(std::time::Duration::from_millis(100)),
+                }
             };
-            
-            // Store output
-            let mut stored = state.output.lock().unwrap();
-            *stored = result.clone();
-            
-            Ok(result)
-        }
-        Err(e) => Err(format!("Failed to execute command: {}", e)),
+
+            {
+                let state = app.state::<ShellState>();
+                state.commands.lock().unwrap().remove(&command_id);
+            }
+
+            app.emit("command-exit", CommandExit { id: command_id, code }).ok();
+        });
+    }
+
+    Ok(command_id)
+}
+
+#[tauri::command]
+fn cancel_command(command_id: String, state: State<ShellState>) -> Result<(), String> {
+    let mut commands = state.commands.lock().unwrap();
+    if let Some(child) = commands.get_mut(&command_id) {
+        child.kill().map_err(|e| e.to_string())
+    } else {
+        Err("Command not found".to_string())
     }
 }
 
 // PTY commands
 #[tauri::command]
-fn create_pty_session(state: State<ShellState>, app: tauri::AppHandle) -> Result<String, String> {
-    state.pty_manager.create_session(app)
+fn create_pty_session(
+    options: Option<PtyOptions>,
+    state: State<ShellState>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    match options {
+        Some(options) => state.pty_manager.create_session_with_options(app, options),
+        None => state.pty_manager.create_session(app),
+    }
 }
 
 #[tauri::command]
@@ -75,6 +163,31 @@ fn close_pty_session(session_id: String, state: State<ShellState>) -> Result<(),
     state.pty_manager.close_session(&session_id)
 }
 
+#[tauri::command]
+fn detach_session(session_id: String, state: State<ShellState>) -> Result<(), String> {
+    state.pty_manager.detach_session(&session_id)
+}
+
+#[tauri::command]
+fn reattach_session(session_id: String, state: State<ShellState>, app: tauri::AppHandle) -> Result<(), String> {
+    state.pty_manager.reattach_session(&session_id, app)
+}
+
+#[tauri::command]
+fn kill_pty_session(session_id: String, state: State<ShellState>) -> Result<(), String> {
+    state.pty_manager.kill_pty_session(&session_id)
+}
+
+#[tauri::command]
+fn set_restart_on_exit(session_id: String, restart: bool, state: State<ShellState>) -> Result<(), String> {
+    state.pty_manager.set_restart_on_exit(&session_id, restart)
+}
+
+#[tauri::command]
+fn get_pty_stats(state: State<ShellState>) -> serde_json::Value {
+    state.pty_manager.get_stats()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
   tauri::Builder::default()
@@ -86,22 +199,30 @@ pub fn run() {
             .build(),
         )?;
       }
-      
+
       println!("Tauri app starting...");
-      
+
+      start_cleanup_task(Arc::clone(&app.state::<ShellState>().pty_manager));
+
       Ok(())
     })
     .manage(ShellState {
-        output: Mutex::new(String::new()),
         pty_manager: Arc::new(PtyManager::new()),
+        commands: Mutex::new(HashMap::new()),
     })
     .invoke_handler(tauri::generate_handler![
-        greet, 
+        greet,
         execute_command,
+        cancel_command,
         create_pty_session,
         write_to_pty,
         resize_pty,
-        close_pty_session
+        close_pty_session,
+        detach_session,
+        reattach_session,
+        kill_pty_session,
+        set_restart_on_exit,
+        get_pty_stats
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
@@ -1,12 +1,91 @@
-use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use crate::pty_backend::{retry_with_backoff, ChildHandle, ExitInfo, LocalBackend, PtyBackend, RemoteBackend, ResizeHandle};
+use std::collections::{HashMap, VecDeque};
 use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
 
+/// Cap on buffered-but-undelivered output kept per session so a detached
+/// tab can reattach and catch up without the manager growing unbounded.
+///
+/// Buffering only starts once `detach_session` has actually been called —
+/// it's not a general crash/disconnect buffer. A webview that closes or
+/// loses its event channel without calling `detach_session` first (e.g. on
+/// an abrupt reload or crash, as opposed to a planned unload) still loses
+/// whatever output arrives in the meantime, since `attached` stays `true`
+/// and nothing gets buffered. Callers that want this protected should call
+/// `detach_session` proactively (e.g. from a window/tab unload handler)
+/// rather than relying on it to cover an unannounced disconnect.
+const SCROLLBACK_CAP_BYTES: usize = 256 * 1024;
+/// Scrollback is replayed in chunks this size so a long-idle reattach
+/// doesn't ship one giant event.
+const REPLAY_CHUNK_BYTES: usize = 8 * 1024;
+/// How often the wait thread polls a session's child for exit.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// How long a session can go without activity before `cleanup_idle_sessions`
+/// considers it eligible for removal.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1800);
+/// How many times `restart_on_exit` is allowed to respawn a session within
+/// `RESTART_WINDOW` before giving up. Bounds a shell that exits immediately
+/// every time (bad cwd, missing binary, a one-line failing script) from
+/// spawning processes and threads forever.
+const MAX_RESTARTS_PER_WINDOW: usize = 5;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+
+type SessionMap = Arc<Mutex<HashMap<String, PtySession>>>;
+
+/// Per-session shell/env/cwd/size configuration for `create_pty_session`.
+/// Replaces the hardcoded `$SHELL`/`cmd.exe`-with-no-env spawn that every
+/// manager used to duplicate.
+#[derive(Debug, serde::Deserialize)]
+pub struct PtyOptions {
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default = "default_rows")]
+    pub rows: u16,
+    #[serde(default = "default_cols")]
+    pub cols: u16,
+}
+
+fn default_rows() -> u16 {
+    24
+}
+
+fn default_cols() -> u16 {
+    80
+}
+
+impl Default for PtyOptions {
+    fn default() -> Self {
+        Self {
+            shell: None,
+            args: Vec::new(),
+            cwd: None,
+            env: HashMap::new(),
+            rows: default_rows(),
+            cols: default_cols(),
+        }
+    }
+}
+
 pub struct PtySession {
     pub id: String,
     writer: Box<dyn Write + Send>,
+    resize: Box<dyn ResizeHandle>,
+    child: Arc<Mutex<Box<dyn ChildHandle>>>,
+    scrollback: Arc<Mutex<VecDeque<u8>>>,
+    attached: Arc<AtomicBool>,
+    restart_on_exit: Arc<AtomicBool>,
+    last_exit: Arc<Mutex<Option<ExitInfo>>>,
+    created_at: Instant,
+    last_activity: Arc<Mutex<Instant>>,
     _reader_thread: std::thread::JoinHandle<()>,
+    _wait_thread: std::thread::JoinHandle<()>,
 }
 
 #[derive(Clone, serde::Serialize)]
@@ -15,90 +94,307 @@ struct TerminalOutput {
     data: String,
 }
 
+#[derive(Clone, serde::Serialize)]
+struct TerminalExit {
+    id: String,
+    code: Option<i32>,
+    signal: Option<i32>,
+}
+
+/// Shared state threaded through a session across restarts, so a respawned
+/// shell keeps the same id, scrollback, activity clock and attach/restart
+/// flags.
+struct SessionHandles {
+    session_id: String,
+    scrollback: Arc<Mutex<VecDeque<u8>>>,
+    attached: Arc<AtomicBool>,
+    restart_on_exit: Arc<AtomicBool>,
+    last_activity: Arc<Mutex<Instant>>,
+    /// Timestamps of recent restarts, oldest first, pruned to
+    /// `RESTART_WINDOW`; shared across every respawn of this session so the
+    /// window spans its whole lifetime rather than resetting each restart.
+    restart_history: Arc<Mutex<VecDeque<Instant>>>,
+}
+
+impl SessionHandles {
+    fn clone_shared(&self) -> Self {
+        Self {
+            session_id: self.session_id.clone(),
+            scrollback: Arc::clone(&self.scrollback),
+            attached: Arc::clone(&self.attached),
+            restart_on_exit: Arc::clone(&self.restart_on_exit),
+            last_activity: Arc::clone(&self.last_activity),
+            restart_history: Arc::clone(&self.restart_history),
+        }
+    }
+}
+
+/// Emits `terminal-output` if the session is attached, otherwise buffers it
+/// into scrollback — the same gate the reader thread applies to real shell
+/// output (below), so synthetic messages like the restart banner also
+/// survive a detach/reattach instead of being silently dropped.
+fn emit_or_buffer(
+    app: &AppHandle,
+    session_id: &str,
+    attached: &AtomicBool,
+    scrollback: &Mutex<VecDeque<u8>>,
+    data: &str,
+) {
+    if attached.load(Ordering::SeqCst) {
+        app.emit("terminal-output", TerminalOutput { id: session_id.to_string(), data: data.to_string() }).ok();
+    } else {
+        let mut backlog = scrollback.lock().unwrap();
+        backlog.extend(data.as_bytes());
+        let overflow = backlog.len().saturating_sub(SCROLLBACK_CAP_BYTES);
+        for _ in 0..overflow {
+            backlog.pop_front();
+        }
+    }
+}
+
+/// Records a restart attempt and reports whether it's still within
+/// `MAX_RESTARTS_PER_WINDOW` restarts per `RESTART_WINDOW`. Pruning happens
+/// here too, so a session that's been stable for a while has its history
+/// forgotten rather than counting against it forever.
+fn restart_allowed(history: &Mutex<VecDeque<Instant>>) -> bool {
+    let mut history = history.lock().unwrap();
+    let now = Instant::now();
+    while history.front().is_some_and(|t| now.duration_since(*t) > RESTART_WINDOW) {
+        history.pop_front();
+    }
+    if history.len() >= MAX_RESTARTS_PER_WINDOW {
+        false
+    } else {
+        history.push_back(now);
+        true
+    }
+}
+
+/// Opens a shell through `backend` and wires up its reader/wait threads,
+/// returning the resulting `PtySession` without inserting it anywhere.
+/// Called both for a brand-new session and, recursively, to respawn one
+/// after a crash; the caller decides how and when the result gets into
+/// `sessions` (see `spawn_shell` and the wait thread's restart path below).
+fn build_session(
+    backend: &Arc<dyn PtyBackend>,
+    sessions: &SessionMap,
+    app: AppHandle,
+    options: &PtyOptions,
+    handles: SessionHandles,
+) -> Result<PtySession, String> {
+    let (mut reader, writer, resize, child) = backend.open(options)?;
+
+    let SessionHandles { session_id, scrollback, attached, restart_on_exit, last_activity, restart_history } = handles;
+
+    let reader_session_id = session_id.clone();
+    let reader_scrollback = Arc::clone(&scrollback);
+    let reader_attached = Arc::clone(&attached);
+    let reader_activity = Arc::clone(&last_activity);
+    let reader_app = app.clone();
+
+    let reader_thread = std::thread::spawn(move || {
+        let mut buffer = [0u8; 1024];
+
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break, // EOF
+                Ok(n) => {
+                    *reader_activity.lock().unwrap() = Instant::now();
+                    let data = String::from_utf8_lossy(&buffer[..n]).to_string();
+                    emit_or_buffer(&reader_app, &reader_session_id, &reader_attached, &reader_scrollback, &data);
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    let child = Arc::new(Mutex::new(child));
+    let last_exit = Arc::new(Mutex::new(None));
+
+    let wait_handles = SessionHandles {
+        session_id: session_id.clone(),
+        scrollback: Arc::clone(&scrollback),
+        attached: Arc::clone(&attached),
+        restart_on_exit: Arc::clone(&restart_on_exit),
+        last_activity: Arc::clone(&last_activity),
+        restart_history: Arc::clone(&restart_history),
+    };
+
+    let wait_thread = {
+        let child = Arc::clone(&child);
+        let last_exit = Arc::clone(&last_exit);
+        let backend = Arc::clone(backend);
+        let sessions = Arc::clone(sessions);
+        let app = app.clone();
+        let session_id = session_id.clone();
+        let options = PtyOptions {
+            shell: options.shell.clone(),
+            args: options.args.clone(),
+            cwd: options.cwd.clone(),
+            env: options.env.clone(),
+            rows: options.rows,
+            cols: options.cols,
+        };
+        let handles = wait_handles;
+
+        std::thread::spawn(move || loop {
+            let exit = child.lock().unwrap().try_wait();
+            match exit {
+                Ok(Some(info)) => {
+                    *last_exit.lock().unwrap() = Some(info);
+                    app.emit("terminal-exit", TerminalExit {
+                        id: session_id.clone(),
+                        code: info.code,
+                        signal: info.signal,
+                    }).ok();
+
+                    // Decide-and-insert happens under the same sessions
+                    // lock close_session uses, so the two are mutually
+                    // exclusive: either close_session removes this id (and
+                    // clears restart_on_exit) before we get here, in which
+                    // case `contains_key` is false and we do nothing, or it
+                    // blocks on this lock and kills whatever we just
+                    // inserted right after — a closed session can never
+                    // come back under its old id.
+                    let mut sessions_guard = sessions.lock().unwrap();
+                    let still_open = sessions_guard.contains_key(&session_id);
+                    let wants_restart = still_open
+                        && handles.restart_on_exit.load(Ordering::SeqCst)
+                        && info.code != Some(0);
+
+                    if wants_restart {
+                        if restart_allowed(&handles.restart_history) {
+                            emit_or_buffer(
+                                &app,
+                                &session_id,
+                                &handles.attached,
+                                &handles.scrollback,
+                                &format!("\r\n[session restarted after exit code {:?}]\r\n", info.code),
+                            );
+
+                            match retry_with_backoff(3, |_| {
+                                build_session(&backend, &sessions, app.clone(), &options, handles.clone_shared())
+                            }) {
+                                Ok(new_session) => {
+                                    sessions_guard.insert(session_id.clone(), new_session);
+                                }
+                                Err(e) => eprintln!("Failed to restart session {session_id}: {e}"),
+                            }
+                        } else {
+                            emit_or_buffer(
+                                &app,
+                                &session_id,
+                                &handles.attached,
+                                &handles.scrollback,
+                                &format!(
+                                    "\r\n[session restart limit reached ({MAX_RESTARTS_PER_WINDOW} restarts within {}s); giving up]\r\n",
+                                    RESTART_WINDOW.as_secs()
+                                ),
+                            );
+                        }
+                    }
+                    break;
+                }
+                Ok(None) => std::thread::sleep(WAIT_POLL_INTERVAL),
+                Err(_) => break,
+            }
+        })
+    };
+
+    Ok(PtySession {
+        id: session_id,
+        writer,
+        resize,
+        child,
+        scrollback,
+        attached,
+        restart_on_exit,
+        last_exit,
+        created_at: Instant::now(),
+        last_activity,
+        _reader_thread: reader_thread,
+        _wait_thread: wait_thread,
+    })
+}
+
+/// Builds a session and inserts it into `sessions` under its id. Used for
+/// brand-new sessions, where there's no concurrent `close_session` to race
+/// against yet (the id doesn't exist in the map until this call).
+fn spawn_shell(
+    backend: &Arc<dyn PtyBackend>,
+    sessions: &SessionMap,
+    app: AppHandle,
+    options: &PtyOptions,
+    handles: SessionHandles,
+) -> Result<(), String> {
+    let session_id = handles.session_id.clone();
+    let session = build_session(backend, sessions, app, options, handles)?;
+    sessions.lock().unwrap().insert(session_id, session);
+    Ok(())
+}
+
 pub struct PtyManager {
-    sessions: Arc<Mutex<std::collections::HashMap<String, PtySession>>>,
+    sessions: SessionMap,
+    backend: Arc<dyn PtyBackend>,
+    max_sessions: usize,
 }
 
 impl PtyManager {
     pub fn new() -> Self {
+        Self::with_backend(Arc::new(LocalBackend::new()))
+    }
+
+    /// Same session lifecycle as `new()`, but every session's shell is
+    /// dialed on `addr` instead of spawned on this machine.
+    pub fn new_remote(addr: String) -> Self {
+        Self::with_backend(Arc::new(RemoteBackend::new(addr)))
+    }
+
+    fn with_backend(backend: Arc<dyn PtyBackend>) -> Self {
         Self {
-            sessions: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            backend,
+            max_sessions: 10,
         }
     }
 
     pub fn create_session(&self, app: AppHandle) -> Result<String, String> {
-        let pty_system = native_pty_system();
-        
-        // Create a new PTY with size
-        let pty_pair = pty_system
-            .openpty(PtySize {
-                rows: 24,
-                cols: 80,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
-            .map_err(|e| e.to_string())?;
+        self.create_session_with_options(app, PtyOptions::default())
+    }
 
-        // Get the shell
-        let shell = if cfg!(target_os = "windows") {
-            "cmd.exe".to_string()
-        } else {
-            std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())
-        };
+    pub fn create_session_with_options(&self, app: AppHandle, options: PtyOptions) -> Result<String, String> {
+        {
+            let sessions = self.sessions.lock().unwrap();
+            if sessions.len() >= self.max_sessions {
+                drop(sessions);
+                self.cleanup_idle_sessions(IDLE_TIMEOUT);
 
-        // Build the command
-        let mut cmd = CommandBuilder::new(shell);
-        
-        // Spawn the shell
-        let _child = pty_pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
-        
-        // Get reader and writer
-        let reader = pty_pair.master.try_clone_reader().map_err(|e| e.to_string())?;
-        let writer = pty_pair.master.take_writer().map_err(|e| e.to_string())?;
-        
-        // Generate session ID
-        let session_id = uuid::Uuid::new_v4().to_string();
-        let session_id_clone = session_id.clone();
-        
-        // Start reader thread
-        let reader_thread = std::thread::spawn(move || {
-            let mut reader = reader;
-            let mut buffer = [0u8; 1024];
-            
-            loop {
-                match reader.read(&mut buffer) {
-                    Ok(0) => break, // EOF
-                    Ok(n) => {
-                        let data = String::from_utf8_lossy(&buffer[..n]).to_string();
-                        
-                        // Emit the data to the frontend
-                        app.emit("terminal-output", TerminalOutput {
-                            id: session_id_clone.clone(),
-                            data,
-                        }).ok();
-                    }
-                    Err(_) => break,
+                let sessions = self.sessions.lock().unwrap();
+                if sessions.len() >= self.max_sessions {
+                    return Err(format!("Maximum number of sessions ({}) reached", self.max_sessions));
                 }
             }
-        });
-        
-        // Create session
-        let session = PtySession {
-            id: session_id.clone(),
-            writer: Box::new(writer),
-            _reader_thread: reader_thread,
+        }
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let handles = SessionHandles {
+            session_id: session_id.clone(),
+            scrollback: Arc::new(Mutex::new(VecDeque::with_capacity(SCROLLBACK_CAP_BYTES))),
+            attached: Arc::new(AtomicBool::new(true)),
+            restart_on_exit: Arc::new(AtomicBool::new(false)),
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+            restart_history: Arc::new(Mutex::new(VecDeque::new())),
         };
-        
-        // Store session
-        self.sessions.lock().unwrap().insert(session_id.clone(), session);
-        
+
+        spawn_shell(&self.backend, &self.sessions, app, &options, handles)?;
         Ok(session_id)
     }
 
     pub fn write_to_session(&self, session_id: &str, data: &str) -> Result<(), String> {
         let mut sessions = self.sessions.lock().unwrap();
-        
+
         if let Some(session) = sessions.get_mut(session_id) {
+            *session.last_activity.lock().unwrap() = Instant::now();
             session.writer.write_all(data.as_bytes()).map_err(|e| e.to_string())?;
             session.writer.flush().map_err(|e| e.to_string())?;
             Ok(())
@@ -107,14 +403,151 @@ impl PtyManager {
         }
     }
 
-    pub fn resize_session(&self, _session_id: &str, _rows: u16, _cols: u16) -> Result<(), String> {
-        // For now, just return OK
-        // TODO: Implement PTY resizing
-        Ok(())
+    pub fn resize_session(&self, session_id: &str, rows: u16, cols: u16) -> Result<(), String> {
+        let sessions = self.sessions.lock().unwrap();
+
+        if let Some(session) = sessions.get(session_id) {
+            *session.last_activity.lock().unwrap() = Instant::now();
+            session.resize.resize(rows, cols)
+        } else {
+            Err("Session not found".to_string())
+        }
     }
 
+    /// Tears down a session outright: removes it from the map, disables
+    /// `restart_on_exit` and kills its shell, all while holding the same
+    /// `sessions` lock the wait thread's restart path takes to decide
+    /// whether to respawn and insert a replacement. That shared lock is
+    /// what actually prevents resurrection, not the atomic flag alone: a
+    /// concurrent exit either sees this entry already gone (so it does
+    /// nothing) or finishes inserting a replacement first, which this call
+    /// then kills as soon as it gets the lock — a closed session never
+    /// comes back under its old id either way.
     pub fn close_session(&self, session_id: &str) -> Result<(), String> {
-        self.sessions.lock().unwrap().remove(session_id);
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.remove(session_id) {
+            session.restart_on_exit.store(false, Ordering::SeqCst);
+            session.child.lock().unwrap().kill().ok();
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Kills a session's shell outright and disables `restart_on_exit` —
+    /// otherwise a session with auto-restart enabled would have the wait
+    /// thread immediately respawn the very process this call was meant to
+    /// stop. The wait thread still observes the exit and emits
+    /// `terminal-exit`.
+    pub fn kill_pty_session(&self, session_id: &str) -> Result<(), String> {
+        let sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get(session_id) {
+            session.restart_on_exit.store(false, Ordering::SeqCst);
+            session.child.lock().unwrap().kill()
+        } else {
+            Err("Session not found".to_string())
+        }
+    }
+
+    /// Enables or disables automatically respawning a session's shell the
+    /// next time it exits non-zero.
+    pub fn set_restart_on_exit(&self, session_id: &str, restart: bool) -> Result<(), String> {
+        let sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get(session_id) {
+            session.restart_on_exit.store(restart, Ordering::SeqCst);
+            Ok(())
+        } else {
+            Err("Session not found".to_string())
+        }
+    }
+
+    /// Stops forwarding a session's output to the frontend without killing
+    /// its shell; output keeps accumulating in the session's scrollback.
+    pub fn detach_session(&self, session_id: &str) -> Result<(), String> {
+        let sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get(session_id) {
+            session.attached.store(false, Ordering::SeqCst);
+            Ok(())
+        } else {
+            Err("Session not found".to_string())
+        }
+    }
+
+    /// Replays a detached session's buffered scrollback as one or more
+    /// `terminal-output` events, then resumes live forwarding. Drains the
+    /// buffer rather than just reading it, so only bytes produced since the
+    /// last detach are ever replayed — a reattach with nothing buffered
+    /// (including one with no prior detach) ships nothing.
+    pub fn reattach_session(&self, session_id: &str, app: AppHandle) -> Result<(), String> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(session_id).ok_or("Session not found".to_string())?;
+
+        let backlog: Vec<u8> = session.scrollback.lock().unwrap().drain(..).collect();
+        for chunk in backlog.chunks(REPLAY_CHUNK_BYTES) {
+            app.emit("terminal-output", TerminalOutput {
+                id: session_id.to_string(),
+                data: String::from_utf8_lossy(chunk).to_string(),
+            }).ok();
+        }
+
+        *session.last_activity.lock().unwrap() = Instant::now();
+        session.attached.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Kills and drops any session that's been inactive longer than
+    /// `idle_duration`, freeing its slot under `max_sessions`.
+    pub fn cleanup_idle_sessions(&self, idle_duration: Duration) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+
+        let idle_ids: Vec<String> = sessions
+            .iter()
+            .filter(|(_, session)| now.duration_since(*session.last_activity.lock().unwrap()) > idle_duration)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in idle_ids {
+            if let Some(session) = sessions.remove(&id) {
+                session.child.lock().unwrap().kill().ok();
+                eprintln!("Cleaned up idle PTY session: {id}");
+            }
+        }
+    }
+
+    /// Reports each session's liveness, last known exit and idle time, for
+    /// surfacing in diagnostics/UI.
+    pub fn get_stats(&self) -> serde_json::Value {
+        let sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+
+        let session_info: Vec<serde_json::Value> = sessions
+            .iter()
+            .map(|(id, session)| {
+                let last_exit = *session.last_exit.lock().unwrap();
+                let last_activity = *session.last_activity.lock().unwrap();
+                serde_json::json!({
+                    "id": id,
+                    "alive": last_exit.is_none(),
+                    "exit_code": last_exit.and_then(|e| e.code),
+                    "exit_signal": last_exit.and_then(|e| e.signal),
+                    "age_seconds": now.duration_since(session.created_at).as_secs(),
+                    "idle_seconds": now.duration_since(last_activity).as_secs(),
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "active_sessions": sessions.len(),
+            "max_sessions": self.max_sessions,
+            "sessions": session_info,
+        })
+    }
+}
+
+/// Periodically sweeps idle sessions, mirroring the standalone cleanup
+/// task the old enhanced manager ran every 5 minutes.
+pub fn start_cleanup_task(pty_manager: Arc<PtyManager>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(300));
+        pty_manager.cleanup_idle_sessions(IDLE_TIMEOUT);
+    });
+}
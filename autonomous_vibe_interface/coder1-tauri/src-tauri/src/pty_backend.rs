@@ -0,0 +1,433 @@
+// Pluggable PTY transports: a session's shell can run on this machine
+// (`LocalBackend`, wrapping the existing `native_pty_system` flow) or on a
+// remote host reached over a small length-prefixed framed protocol
+// (`RemoteBackend`). `PtyManager` talks to whichever backend it's given
+// through the `PtyBackend` trait, so `create_pty_session`/`write_to_pty`/
+// `resize_pty`/`close_pty_session` behave identically either way.
+
+use crate::pty::PtyOptions;
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A single message on the wire: `[u32 len][u8 kind][payload]`, length and
+/// kind describing `payload` as below.
+#[derive(Debug, Clone)]
+enum Frame {
+    Stdout(Vec<u8>),
+    Stdin(Vec<u8>),
+    Resize(u16, u16),
+    Close,
+    Exit(i32),
+}
+
+impl Frame {
+    const KIND_STDOUT: u8 = 0;
+    const KIND_STDIN: u8 = 1;
+    const KIND_RESIZE: u8 = 2;
+    const KIND_CLOSE: u8 = 3;
+    const KIND_EXIT: u8 = 4;
+
+    fn encode(&self) -> Vec<u8> {
+        let (kind, payload) = match self {
+            Frame::Stdout(bytes) => (Self::KIND_STDOUT, bytes.clone()),
+            Frame::Stdin(bytes) => (Self::KIND_STDIN, bytes.clone()),
+            Frame::Resize(rows, cols) => {
+                let mut payload = Vec::with_capacity(4);
+                payload.extend_from_slice(&rows.to_be_bytes());
+                payload.extend_from_slice(&cols.to_be_bytes());
+                (Self::KIND_RESIZE, payload)
+            }
+            Frame::Close => (Self::KIND_CLOSE, Vec::new()),
+            Frame::Exit(code) => (Self::KIND_EXIT, code.to_be_bytes().to_vec()),
+        };
+        let mut out = Vec::with_capacity(5 + payload.len());
+        out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        out.push(kind);
+        out.extend_from_slice(&payload);
+        out
+    }
+
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut len_buf = [0u8; 4];
+        r.read_exact(&mut len_buf)?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut kind_buf = [0u8; 1];
+        r.read_exact(&mut kind_buf)?;
+        let mut payload = vec![0u8; len];
+        r.read_exact(&mut payload)?;
+
+        Ok(match kind_buf[0] {
+            Self::KIND_STDOUT => Frame::Stdout(payload),
+            Self::KIND_STDIN => Frame::Stdin(payload),
+            Self::KIND_RESIZE if payload.len() == 4 => Frame::Resize(
+                u16::from_be_bytes([payload[0], payload[1]]),
+                u16::from_be_bytes([payload[2], payload[3]]),
+            ),
+            Self::KIND_CLOSE => Frame::Close,
+            Self::KIND_EXIT if payload.len() == 4 => {
+                Frame::Exit(i32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]))
+            }
+            other => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad frame kind {other}")))
+            }
+        })
+    }
+}
+
+/// Lets a backend be resized after it's already open, without going
+/// through the `Write` side of the session.
+pub trait ResizeHandle: Send {
+    fn resize(&self, rows: u16, cols: u16) -> Result<(), String>;
+}
+
+/// Why a session's shell stopped running.
+#[derive(Debug, Clone, Copy)]
+pub struct ExitInfo {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+}
+
+/// Lets a backend's shell process be waited on and killed, independent of
+/// its reader/writer.
+pub trait ChildHandle: Send {
+    /// Non-blocking: `Ok(None)` means still running.
+    fn try_wait(&mut self) -> Result<Option<ExitInfo>, String>;
+    fn kill(&mut self) -> Result<(), String>;
+}
+
+/// Where a PTY session's shell actually runs. `PtyManager` is backend
+/// agnostic: it just needs a reader/writer/resize/child handle back.
+pub trait PtyBackend: Send + Sync {
+    #[allow(clippy::type_complexity)]
+    fn open(
+        &self,
+        options: &PtyOptions,
+    ) -> Result<
+        (
+            Box<dyn Read + Send>,
+            Box<dyn Write + Send>,
+            Box<dyn ResizeHandle>,
+            Box<dyn ChildHandle>,
+        ),
+        String,
+    >;
+}
+
+/// Retries `f` with the same exponential backoff (100ms * 2^attempt)
+/// `LocalBackend` uses when a local PTY fails to open, so a dropped
+/// remote transport reconnects the same way.
+pub fn retry_with_backoff<T>(
+    attempts: u32,
+    mut f: impl FnMut(u32) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut last_error = String::new();
+    for attempt in 0..attempts {
+        if attempt > 0 {
+            std::thread::sleep(Duration::from_millis(100 * 2_u64.pow(attempt)));
+        }
+        match f(attempt) {
+            Ok(value) => return Ok(value),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(format!("failed after {attempts} attempts: {last_error}"))
+}
+
+struct LocalResizeHandle {
+    master: Mutex<Box<dyn portable_pty::MasterPty + Send>>,
+}
+
+impl ResizeHandle for LocalResizeHandle {
+    fn resize(&self, rows: u16, cols: u16) -> Result<(), String> {
+        self.master
+            .lock()
+            .unwrap()
+            .resize(PtySize { rows, cols, pixel_width: 0, pixel_height: 0 })
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Shell used when `PtyOptions::shell` isn't set.
+pub fn default_shell() -> String {
+    if cfg!(target_os = "windows") {
+        "cmd.exe".to_string()
+    } else {
+        std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())
+    }
+}
+
+/// Spawns the shell on this machine via `native_pty_system`, i.e. today's
+/// behavior before remote backends existed.
+pub struct LocalBackend;
+
+impl LocalBackend {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+struct LocalChildHandle {
+    child: Box<dyn portable_pty::Child + Send + Sync>,
+}
+
+impl ChildHandle for LocalChildHandle {
+    fn try_wait(&mut self) -> Result<Option<ExitInfo>, String> {
+        match self.child.try_wait() {
+            Ok(Some(status)) => Ok(Some(ExitInfo { code: Some(status.exit_code() as i32), signal: None })),
+            Ok(None) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn kill(&mut self) -> Result<(), String> {
+        self.child.kill().map_err(|e| e.to_string())
+    }
+}
+
+impl PtyBackend for LocalBackend {
+    fn open(
+        &self,
+        options: &PtyOptions,
+    ) -> Result<
+        (
+            Box<dyn Read + Send>,
+            Box<dyn Write + Send>,
+            Box<dyn ResizeHandle>,
+            Box<dyn ChildHandle>,
+        ),
+        String,
+    > {
+        let size = PtySize { rows: options.rows, cols: options.cols, pixel_width: 0, pixel_height: 0 };
+        let pty_pair = retry_with_backoff(3, |_| native_pty_system().openpty(size).map_err(|e| e.to_string()))?;
+
+        let shell = options.shell.clone().unwrap_or_else(default_shell);
+        let mut cmd = CommandBuilder::new(shell);
+        cmd.args(&options.args);
+        if let Some(cwd) = &options.cwd {
+            cmd.cwd(cwd);
+        }
+        for (key, value) in &options.env {
+            cmd.env(key, value);
+        }
+
+        let child = pty_pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
+
+        let reader = pty_pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+        let writer = pty_pair.master.take_writer().map_err(|e| e.to_string())?;
+        let resize = LocalResizeHandle { master: Mutex::new(pty_pair.master) };
+
+        Ok((
+            Box::new(reader),
+            Box::new(writer),
+            Box::new(resize),
+            Box::new(LocalChildHandle { child }),
+        ))
+    }
+}
+
+/// Shared connection state for one remote session. `RemoteReader`,
+/// `RemoteWriter`, `RemoteResizeHandle` and `RemoteChildHandle` all hold an
+/// `Arc` of this so that when the reader detects a dropped transport and
+/// re-dials, every other handle picks up the new socket too instead of
+/// writing into a dead one.
+struct RemoteConnection {
+    addr: String,
+    retry_attempts: u32,
+    stream: Mutex<TcpStream>,
+    /// Last negotiated terminal size, resent on every `reconnect()` since
+    /// the remote side has no memory of it once the socket is replaced.
+    size: Mutex<(u16, u16)>,
+    /// Set from a clean `Frame::Exit` — the remote shell actually exited.
+    exit_code: Mutex<Option<i32>>,
+    /// Set when a dropped transport couldn't be re-established after
+    /// `retry_attempts` — distinct from `exit_code` since the shell itself
+    /// may still be running on the remote host.
+    transport_dead: Mutex<bool>,
+}
+
+impl RemoteConnection {
+    fn dial(addr: &str, retry_attempts: u32) -> Result<TcpStream, String> {
+        let addr = addr.to_string();
+        retry_with_backoff(retry_attempts, move |attempt| {
+            if attempt > 0 {
+                eprintln!("Retrying connection to {addr}, attempt {}", attempt + 1);
+            }
+            TcpStream::connect(&addr).map_err(|e| e.to_string())
+        })
+    }
+
+    fn connect(addr: String, retry_attempts: u32, rows: u16, cols: u16) -> Result<Self, String> {
+        let stream = Self::dial(&addr, retry_attempts)?;
+        Ok(Self {
+            addr,
+            retry_attempts,
+            stream: Mutex::new(stream),
+            size: Mutex::new((rows, cols)),
+            exit_code: Mutex::new(None),
+            transport_dead: Mutex::new(false),
+        })
+    }
+
+    /// Re-dials after the transport drops mid-session (a read/write error,
+    /// as opposed to a clean `Exit`/`Close` frame), reusing the same
+    /// backoff as the initial dial. Resends the last negotiated size, since
+    /// the new socket's remote side starts with no notion of it. Marks the
+    /// connection dead if every attempt fails, so `RemoteChildHandle::try_wait`
+    /// can surface it as an exit instead of spinning forever waiting on a
+    /// socket that's gone.
+    fn reconnect(&self) -> Result<(), String> {
+        match Self::dial(&self.addr, self.retry_attempts) {
+            Ok(stream) => {
+                *self.stream.lock().unwrap() = stream;
+                let (rows, cols) = *self.size.lock().unwrap();
+                self.write_frame(&Frame::Resize(rows, cols))
+            }
+            Err(e) => {
+                *self.transport_dead.lock().unwrap() = true;
+                Err(e)
+            }
+        }
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        // Read through a clone of the socket so the lock isn't held while
+        // blocked waiting for output; writers can still send frames meanwhile.
+        let mut stream = self.stream.lock().unwrap().try_clone()?;
+        Frame::read_from(&mut stream)
+    }
+
+    fn write_frame(&self, frame: &Frame) -> Result<(), String> {
+        self.stream.lock().unwrap().write_all(&frame.encode()).map_err(|e| e.to_string())
+    }
+}
+
+struct RemoteResizeHandle {
+    conn: Arc<RemoteConnection>,
+}
+
+impl ResizeHandle for RemoteResizeHandle {
+    fn resize(&self, rows: u16, cols: u16) -> Result<(), String> {
+        *self.conn.size.lock().unwrap() = (rows, cols);
+        self.conn.write_frame(&Frame::Resize(rows, cols))
+    }
+}
+
+struct RemoteReader {
+    conn: Arc<RemoteConnection>,
+    pending: VecDeque<u8>,
+}
+
+impl Read for RemoteReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() {
+            match self.conn.read_frame() {
+                Ok(Frame::Stdout(bytes)) => self.pending.extend(bytes),
+                Ok(Frame::Exit(code)) => {
+                    *self.conn.exit_code.lock().unwrap() = Some(code);
+                    return Ok(0);
+                }
+                Ok(Frame::Close) => return Ok(0),
+                Ok(Frame::Stdin(_)) | Ok(Frame::Resize(..)) => continue,
+                Err(_) => {
+                    // The socket dropped without a clean Exit/Close frame —
+                    // reconnect rather than treating this as EOF, so the
+                    // wait thread doesn't spin forever on a dead transport.
+                    self.conn.reconnect().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                }
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        for slot in buf.iter_mut().take(n) {
+            *slot = self.pending.pop_front().unwrap();
+        }
+        Ok(n)
+    }
+}
+
+struct RemoteChildHandle {
+    conn: Arc<RemoteConnection>,
+}
+
+impl ChildHandle for RemoteChildHandle {
+    fn try_wait(&mut self) -> Result<Option<ExitInfo>, String> {
+        if let Some(code) = *self.conn.exit_code.lock().unwrap() {
+            return Ok(Some(ExitInfo { code: Some(code), signal: None }));
+        }
+        if *self.conn.transport_dead.lock().unwrap() {
+            return Ok(Some(ExitInfo { code: None, signal: None }));
+        }
+        Ok(None)
+    }
+
+    fn kill(&mut self) -> Result<(), String> {
+        self.conn.write_frame(&Frame::Close)
+    }
+}
+
+struct RemoteWriter {
+    conn: Arc<RemoteConnection>,
+}
+
+impl Write for RemoteWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.conn
+            .write_frame(&Frame::Stdin(buf.to_vec()))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Dials a remote host speaking the framed PTY protocol and proxies
+/// reads/writes/resizes over that connection, reconnecting with
+/// [`retry_with_backoff`] the same way a local PTY retries `openpty`.
+pub struct RemoteBackend {
+    addr: String,
+    retry_attempts: u32,
+}
+
+impl RemoteBackend {
+    pub fn new(addr: String) -> Self {
+        Self { addr, retry_attempts: 3 }
+    }
+}
+
+impl PtyBackend for RemoteBackend {
+    // The framed protocol only carries terminal size today, so a remote
+    // shell's command/cwd/env are whatever the remote host is configured
+    // to launch; only `options.rows`/`options.cols` apply here.
+    fn open(
+        &self,
+        options: &PtyOptions,
+    ) -> Result<
+        (
+            Box<dyn Read + Send>,
+            Box<dyn Write + Send>,
+            Box<dyn ResizeHandle>,
+            Box<dyn ChildHandle>,
+        ),
+        String,
+    > {
+        let conn = Arc::new(RemoteConnection::connect(
+            self.addr.clone(),
+            self.retry_attempts,
+            options.rows,
+            options.cols,
+        )?);
+        conn.write_frame(&Frame::Resize(options.rows, options.cols))?;
+
+        Ok((
+            Box::new(RemoteReader { conn: Arc::clone(&conn), pending: VecDeque::new() }),
+            Box::new(RemoteWriter { conn: Arc::clone(&conn) }),
+            Box::new(RemoteResizeHandle { conn: Arc::clone(&conn) }),
+            Box::new(RemoteChildHandle { conn }),
+        ))
+    }
+}